@@ -0,0 +1,105 @@
+// Headless CLI for the ncmdump-gui decryption pipeline, for scripting and
+// batch server-side use without launching the Tauri GUI.
+// ncmdump-gui 解密流程的无界面命令行工具，便于脚本化和服务端批量使用，无需启动 Tauri GUI。
+
+use clap::{Parser, ValueEnum};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// How to handle the decrypted cover image, mirroring [`ncmdump_gui::CoverMode`]
+#[derive(Clone, Copy, ValueEnum)]
+enum Cover {
+    Embed,
+    SeparateFile,
+    None,
+}
+
+impl From<Cover> for ncmdump_gui::CoverMode {
+    fn from(cover: Cover) -> Self {
+        match cover {
+            Cover::Embed => ncmdump_gui::CoverMode::Embed,
+            Cover::SeparateFile => ncmdump_gui::CoverMode::SeparateFile,
+            Cover::None => ncmdump_gui::CoverMode::None,
+        }
+    }
+}
+
+/// Decrypt NetEase Cloud Music `.ncm` files
+#[derive(Parser)]
+#[command(name = "ncmdump", version, about)]
+struct Cli {
+    /// Input `.ncm` file or a directory to scan recursively
+    input: PathBuf,
+
+    /// Directory to write the decrypted files into
+    #[arg(short, long)]
+    output_dir: PathBuf,
+
+    /// Verify each cover image's CRC-32 before writing it out
+    #[arg(long)]
+    verify: bool,
+
+    /// How to handle the decrypted cover image
+    #[arg(long, value_enum, default_value = "embed")]
+    cover: Cover,
+
+    /// Skip files whose output audio file already exists
+    #[arg(long)]
+    skip_existing: bool,
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if let Err(e) = fs::create_dir_all(&cli.output_dir) {
+        eprintln!("failed to create output directory: {}", e);
+        std::process::exit(1);
+    }
+
+    let files = ncm_files(&cli.input);
+    if files.is_empty() {
+        eprintln!("no .ncm files found at {}", cli.input.display());
+        std::process::exit(1);
+    }
+
+    let options = ncmdump_gui::DumpOptions {
+        verify: cli.verify,
+        cover: cli.cover.into(),
+        skip_existing: cli.skip_existing,
+    };
+
+    let mut failures = 0;
+    for file in &files {
+        let output_dir = cli.output_dir.to_string_lossy();
+        match ncmdump_gui::dump_with_options(&file.to_string_lossy(), &output_dir, &options) {
+            Ok(()) => println!("dumped {}", file.display()),
+            Err(e) => {
+                eprintln!("failed to dump {}: {}", file.display(), e);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn ncm_files(input: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if input.is_file() {
+        if input.extension().and_then(|s| s.to_str()) == Some("ncm") {
+            files.push(input.to_path_buf());
+        }
+    } else if input.is_dir() {
+        for entry in WalkDir::new(input)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("ncm"))
+        {
+            files.push(entry.path().to_path_buf());
+        }
+    }
+    files
+}
@@ -0,0 +1,442 @@
+use aes::Aes128;
+use base64::prelude::*;
+use block_modes::block_padding::Pkcs7;
+use block_modes::{BlockMode, Ecb};
+use byteorder::{LittleEndian, ReadBytesExt};
+use crc::{Crc, CRC_32_ISO_HDLC};
+use hex::FromHex;
+use id3::TagLike;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, prelude::*, BufWriter, Seek, SeekFrom};
+use std::path::Path;
+
+const CORE_KEY: &str = "687A4852416D736F356B496E62617857";
+const META_KEY: &str = "2331346C6A6B5F215C5D2630553C2728";
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// How the decrypted cover image should be handled.
+/// 解密出的封面图片应如何处理。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+pub enum CoverMode {
+    /// Embed the cover into the audio file's tags only.
+    #[default]
+    Embed,
+    /// Write the cover out as its own sidecar file (`.jpg`/`.png`), no tag embedding.
+    SeparateFile,
+    /// Discard the cover image entirely.
+    None,
+}
+
+/// Options controlling how a single `.ncm` file is dumped.
+/// 控制单个 `.ncm` 文件解密行为的选项。
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct DumpOptions {
+    /// Verify the cover image against its stored CRC-32 before writing it out.
+    pub verify: bool,
+    /// How to handle the decrypted cover image.
+    pub cover: CoverMode,
+    /// Skip files whose output audio file already exists.
+    pub skip_existing: bool,
+}
+
+/// Run the full decryption pipeline for a single `.ncm` file and write the
+/// tagged audio (plus cover art) into `output_dir`.
+/// 对单个 `.ncm` 文件运行完整的解密流程，并将带标签的音频（及封面图片）写入 `output_dir`。
+pub fn dump(file_path: &str, output_dir: &str) -> io::Result<()> {
+    dump_with_options(file_path, output_dir, &DumpOptions::default())
+}
+
+/// Same as [`dump`], but with explicit [`DumpOptions`].
+pub fn dump_with_options(file_path: &str, output_dir: &str, options: &DumpOptions) -> io::Result<()> {
+    // Open the .ncm file 打开 .ncm 文件
+    let mut f = open_ncm_file(file_path)?;
+
+    // Decrypt the key 解密 key
+    let key_box = decrypt_key(&mut f)?;
+
+    // Decrypt the metadata 解密元数据
+    let meta_data = decrypt_meta_data(&mut f)?;
+
+    let file_name_prefix = Path::new(output_dir)
+        .join(Path::new(file_path).file_stem().unwrap())
+        .to_str()
+        .unwrap()
+        .to_string();
+    let format = meta_data["format"].as_str().unwrap();
+    let audio_path = format!("{}.{}", file_name_prefix, format);
+
+    if options.skip_existing && Path::new(&audio_path).exists() {
+        return Ok(());
+    }
+
+    // Decrypt the image data 解密图片数据
+    let (image_data, stored_crc32) = decrypt_image_data(&mut f)?;
+    if options.verify {
+        if let Some(computed_crc32) = crc32_mismatch(&image_data, stored_crc32) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: cover image CRC32 mismatch (expected {:08x}, computed {:08x})",
+                    file_path, stored_crc32, computed_crc32
+                ),
+            ));
+        }
+    }
+
+    // Write the cover image out as its own file, named with the extension
+    // matching its sniffed magic bytes 将封面图片写为单独的文件，根据嗅探到的魔数使用正确的扩展名
+    if options.cover == CoverMode::SeparateFile && !image_data.is_empty() {
+        let image_path = format!("{}.{}", file_name_prefix, cover_extension(&image_data));
+        let mut image_file = File::create(image_path)?;
+        image_file.write_all(&image_data)?;
+    }
+
+    // Decrypt the audio data, streaming it straight into the output file
+    // 解密音频数据，边解密边写入输出文件
+    let audio_file = File::create(&audio_path)?;
+    let mut audio_writer = BufWriter::new(audio_file);
+    decrypt_file_data(&mut f, &key_box, &mut audio_writer)?;
+    audio_writer.flush()?;
+    drop(audio_writer);
+
+    // Embed the parsed metadata and cover art into the written audio file
+    // 将解析出的元数据和封面图片写入音频文件的标签
+    let embed_image = if options.cover == CoverMode::Embed {
+        image_data.as_slice()
+    } else {
+        &[]
+    };
+    match format {
+        "mp3" => tag_mp3(&audio_path, &meta_data, embed_image)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
+        "flac" => tag_flac(&audio_path, &meta_data, embed_image)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
+        _ => {}
+    }
+
+    Ok(())
+}
+
+// Collect the artist names out of the `artist` field, which is a JSON array
+// of `[name, id]` pairs 从 `artist` 字段中提取歌手名，该字段是一个 `[name, id]` 数组
+fn artist_names(meta_data: &Value) -> Vec<String> {
+    meta_data["artist"]
+        .as_array()
+        .map(|artists| {
+            artists
+                .iter()
+                .filter_map(|pair| pair.get(0).and_then(|name| name.as_str()))
+                .map(|name| name.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Sniff the image's magic bytes rather than assuming PNG, since NCM covers
+// are usually JPEG 通过嗅探魔数而非假定为 PNG 来判断图片格式，因为 NCM 封面通常是 JPEG
+fn cover_mime(image_data: &[u8]) -> &'static str {
+    if image_data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
+
+fn cover_extension(image_data: &[u8]) -> &'static str {
+    match cover_mime(image_data) {
+        "image/png" => "png",
+        _ => "jpg",
+    }
+}
+
+fn tag_mp3(audio_path: &str, meta_data: &Value, image_data: &[u8]) -> id3::Result<()> {
+    let mut tag = id3::Tag::new();
+
+    if let Some(title) = meta_data["musicName"].as_str() {
+        tag.set_title(title);
+    }
+    let artists = artist_names(meta_data);
+    if !artists.is_empty() {
+        tag.set_artist(artists.join("/"));
+    }
+    if let Some(album) = meta_data["album"].as_str() {
+        tag.set_album(album);
+    }
+    if !image_data.is_empty() {
+        tag.add_frame(id3::frame::Picture {
+            mime_type: cover_mime(image_data).to_string(),
+            picture_type: id3::frame::PictureType::CoverFront,
+            description: String::new(),
+            data: image_data.to_vec(),
+        });
+    }
+
+    tag.write_to_path(audio_path, id3::Version::Id3v24)
+}
+
+fn tag_flac(audio_path: &str, meta_data: &Value, image_data: &[u8]) -> Result<(), metaflac::Error> {
+    let mut tag = metaflac::Tag::read_from_path(audio_path)?;
+
+    let comments = tag.vorbis_comments_mut();
+    if let Some(title) = meta_data["musicName"].as_str() {
+        comments.set_title(vec![title.to_string()]);
+    }
+    let artists = artist_names(meta_data);
+    if !artists.is_empty() {
+        comments.set_artist(artists);
+    }
+    if let Some(album) = meta_data["album"].as_str() {
+        comments.set_album(vec![album.to_string()]);
+    }
+    if !image_data.is_empty() {
+        tag.add_picture(
+            cover_mime(image_data).to_string(),
+            metaflac::block::PictureType::CoverFront,
+            image_data.to_vec(),
+        );
+    }
+
+    tag.save()
+}
+
+pub fn open_ncm_file(file_path: &str) -> io::Result<File> {
+    let mut f = File::open(file_path)?;
+
+    // Check the file header 检查文件头
+    let mut header = [0; 8];
+    f.read_exact(&mut header)?;
+    if &header != b"CTENFDAM" {
+        return Err(io::Error::new(io::ErrorKind::Other, "Invalid file header"));
+    }
+
+    Ok(f)
+}
+
+pub fn decrypt_key(f: &mut File) -> io::Result<Vec<u8>> {
+    // Move file pointer, skipping two bytes 移动文件指针，跳过两个字节
+    f.seek(SeekFrom::Current(2))?;
+
+    // Read key length using byteorder for little-endian u32 读取 key 长度，使用 byteorder 库来读取小端序的 u32
+    let key_length = f.read_u32::<LittleEndian>()?;
+
+    // Decrypt key data 调用 decrypt_key_data 方法
+    let key_data = decrypt_key_data(f, key_length)?;
+
+    // Generate key box 调用 generate_key_box 方法
+    Ok(generate_key_box(&key_data))
+}
+
+fn decrypt_key_data(f: &mut File, key_length: u32) -> io::Result<Vec<u8>> {
+    // Read the key data 读取 key 数据
+    let mut key_data = vec![0; key_length as usize];
+    f.read_exact(&mut key_data)?;
+
+    // XOR each byte with 0x64 对每个字节进行异或操作
+    for byte in &mut key_data {
+        *byte ^= 0x64;
+    }
+
+    // Create an AES decryptor instance 创建 AES 解密器实例
+    let core_key = Vec::from_hex(CORE_KEY).unwrap();
+    let cipher = Ecb::<Aes128, Pkcs7>::new_from_slices(&core_key, Default::default())
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    // Decrypt the data 解密数据
+    let decrypted_data = cipher
+        .decrypt_vec(&key_data)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    // Skip the first 17 bytes and return the rest 跳过前 17 个字节并返回剩余部分
+    Ok(decrypted_data[17..].to_vec())
+}
+
+fn generate_key_box(key_data: &[u8]) -> Vec<u8> {
+    let key_length = key_data.len();
+    let mut key_box: Vec<u8> = (0..=255).collect();
+    let mut last_byte: u8 = 0;
+    let mut key_offset: usize = 0;
+
+    for i in 0..256 {
+        let swap = key_box[i];
+        let c = (swap as usize + last_byte as usize + key_data[key_offset] as usize) & 0xFF;
+        key_offset += 1;
+        if key_offset >= key_length {
+            key_offset = 0;
+        }
+        key_box[i] = key_box[c];
+        key_box[c] = swap;
+        last_byte = c as u8;
+    }
+
+    key_box
+}
+
+pub fn decrypt_meta_data(f: &mut File) -> io::Result<Value> {
+    // Read the metadata length 读取元数据长度
+    let meta_length = f.read_u32::<LittleEndian>()?;
+
+    // Read the metadata 读取元数据
+    let mut meta_data = vec![0; meta_length as usize];
+    f.read_exact(&mut meta_data)?;
+
+    // XOR operation for each byte 对每个字节进行异或操作
+    for byte in &mut meta_data {
+        *byte ^= 0x63;
+    }
+
+    // Base64 decode, skipping the first 22 bytes Base64 解码，跳过前 22 个字节
+    meta_data = BASE64_STANDARD
+        .decode(&meta_data[22..])
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    // Set up the AES decryptor 设置 AES 解密器
+    let meta_key = Vec::from_hex(META_KEY).unwrap();
+    let cipher = Ecb::<Aes128, Pkcs7>::new_from_slices(&meta_key, Default::default())
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    // Decrypt the data 解密数据
+    let decrypted_data = cipher
+        .decrypt_vec(&meta_data)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    // Remove the first 6 bytes and convert to a UTF-8 string
+    // 去除前 6 个字节并转换为 UTF-8 字符串
+    let decrypted_str = String::from_utf8(decrypted_data[6..].to_vec())
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    // Parse JSON 解析 JSON
+    serde_json::from_str(&decrypted_str).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+/// Compares a computed CRC-32 against the value stored ahead of the cover
+/// image. Several reference dumpers leave that field unused and zeroed, so a
+/// stored value of `0` is treated as "not present" rather than a guaranteed
+/// mismatch — only a non-zero, differing value is reported. Returns the
+/// computed checksum when there is a genuine mismatch to report.
+/// 将计算出的 CRC-32 与封面图片前存储的值比较。部分参考实现中该字段未被使用且为
+/// 零，因此存储值为 `0` 时视为"未提供"而非必然不匹配——只有非零且不一致的值才会
+/// 被上报。存在真实不匹配时返回计算出的校验值。
+fn crc32_mismatch(image_data: &[u8], stored_crc32: u32) -> Option<u32> {
+    if stored_crc32 == 0 {
+        return None;
+    }
+    let computed_crc32 = CRC32.checksum(image_data);
+    if computed_crc32 == stored_crc32 {
+        None
+    } else {
+        Some(computed_crc32)
+    }
+}
+
+/// Returns the decrypted image bytes along with the CRC-32 value stored
+/// alongside them, so callers can optionally verify integrity.
+/// 返回解密后的图片数据，以及随图片一同存储的 CRC-32 值，供调用方按需校验完整性。
+pub fn decrypt_image_data(f: &mut File) -> io::Result<(Vec<u8>, u32)> {
+    // Read the CRC32 value 读取 CRC32 值
+    let crc32 = f.read_u32::<LittleEndian>()?;
+
+    // Skip 5 bytes 跳过 5 个字节
+    f.seek(SeekFrom::Current(5))?;
+
+    // Read the image size 读取图片大小
+    let image_size = f.read_u32::<LittleEndian>()?;
+
+    // Read the image data 读取图片数据
+    let mut image_data = vec![0; image_size as usize];
+    f.read_exact(&mut image_data)?;
+
+    Ok((image_data, crc32))
+}
+
+// Decrypt the audio stream chunk by chunk, writing each chunk straight to
+// `output` so memory use stays flat regardless of track length. The keystream
+// index `j` only depends on the in-chunk offset `(i + 1) & 0xFF`, so it stays
+// correct across chunk boundaries as long as every chunk but the last is a
+// full `0x8000` bytes. `Read::read` is allowed to return short of that even
+// mid-stream, so each chunk is topped up in a loop rather than trusting a
+// single `read` call to fill the buffer.
+// 按块解密音频流，边解密边写入 `output`，使内存占用与曲目长度无关。密钥流下标
+// `j` 只取决于块内偏移 `(i + 1) & 0xFF`，因此只要除最后一块外每块都是完整的
+// `0x8000` 字节，跨块边界时仍然正确。`Read::read` 即使在流中间也可能返回不足的
+// 字节数，因此每个块都通过循环补满，而不是信任单次 `read` 调用填满缓冲区。
+pub fn decrypt_file_data<W: Write>(f: &mut File, key_box: &[u8], output: &mut W) -> io::Result<()> {
+    let mut chunk = [0u8; 0x8000];
+    loop {
+        let mut filled = 0;
+        while filled < chunk.len() {
+            match f.read(&mut chunk[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+        // Decrypt the chunk of audio data 解密音频数据块
+        for i in 0..filled {
+            let j = (i + 1) & 0xFF;
+            chunk[i] ^= key_box[(key_box[j] as usize
+                + key_box[(key_box[j] as usize + j) & 0xFF] as usize)
+                & 0xFF];
+        }
+        output.write_all(&chunk[..filled])?;
+        if filled < chunk.len() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn artist_names_collects_the_name_from_each_name_id_pair() {
+        let meta_data = json!({
+            "artist": [["Artist One", 1], ["Artist Two", 2]],
+        });
+        assert_eq!(artist_names(&meta_data), vec!["Artist One", "Artist Two"]);
+    }
+
+    #[test]
+    fn artist_names_is_empty_when_the_field_is_missing() {
+        assert_eq!(artist_names(&json!({})), Vec::<String>::new());
+    }
+
+    #[test]
+    fn cover_mime_detects_png_by_magic_bytes() {
+        let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0];
+        assert_eq!(cover_mime(&png), "image/png");
+        assert_eq!(cover_extension(&png), "png");
+    }
+
+    #[test]
+    fn cover_mime_defaults_to_jpeg_for_anything_else() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0];
+        assert_eq!(cover_mime(&jpeg), "image/jpeg");
+        assert_eq!(cover_extension(&jpeg), "jpg");
+    }
+
+    #[test]
+    fn crc32_mismatch_is_none_when_the_stored_value_is_unused() {
+        assert_eq!(crc32_mismatch(b"some image bytes", 0), None);
+    }
+
+    #[test]
+    fn crc32_mismatch_is_none_when_the_checksums_agree() {
+        let data = b"some image bytes";
+        let stored = CRC32.checksum(data);
+        assert_eq!(crc32_mismatch(data, stored), None);
+    }
+
+    #[test]
+    fn crc32_mismatch_reports_the_computed_checksum_on_disagreement() {
+        let data = b"some image bytes";
+        let computed = CRC32.checksum(data);
+        assert_eq!(crc32_mismatch(data, computed.wrapping_add(1)), Some(computed));
+    }
+}